@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::io::IsTerminal;
 use std::path::PathBuf;
 use std::str::FromStr;
 
@@ -105,6 +106,82 @@ impl DepthRange {
         };
         start_ok && end_ok
     }
+
+    /// インデント計算の基準として使う、実質的な開始深さ
+    fn effective_start(&self) -> usize {
+        match self.start {
+            Endpoint::Inclusive(start) => start,
+            Endpoint::Exclusive(start) => start + 1,
+        }
+    }
+}
+
+/// `DepthRange` のカンマ区切りの和集合を表す構造体
+///
+/// `--depth '0,2,4..6'` のように、連続しない複数の深さ範囲をまとめて指定できる
+#[derive(Debug, Clone)]
+pub struct DepthSelection(Vec<DepthRange>);
+
+impl FromStr for DepthSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ranges = s
+            .split(',')
+            .map(str::parse::<DepthRange>)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(DepthSelection(ranges))
+    }
+}
+
+impl DepthSelection {
+    /// いずれかの区間が指定された深さを含むかどうかを判定する
+    pub fn contains(&self, depth: usize) -> bool {
+        self.0.iter().any(|range| range.contains(depth))
+    }
+
+    /// インデント計算の基準となる深さ（各区間の実質的な開始深さの最小値）を返す
+    pub fn base_depth(&self) -> usize {
+        self.0
+            .iter()
+            .map(DepthRange::effective_start)
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// `tree` コマンドの出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// これまで通りのインデント付きテキスト表示
+    Text,
+    /// 構造化されたJSON表示
+    Json,
+    /// Lisp風のS式表示
+    Sexp,
+}
+
+/// 色付け出力をいつ行うか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Color {
+    /// 標準出力がターミナルに接続されている場合のみ色付けする
+    Auto,
+    /// 常に色付けする
+    Always,
+    /// 色付けしない
+    Never,
+}
+
+impl Color {
+    /// 実際に色付けを行うかどうかを解決する
+    fn resolve(self) -> bool {
+        match self {
+            Color::Always => true,
+            Color::Never => false,
+            Color::Auto => std::io::stdout().is_terminal(),
+        }
+    }
 }
 
 /// 表示設定を管理する構造体
@@ -124,6 +201,10 @@ pub struct DisplayConfig {
     pub show_sql_separator: bool,
     /// 各ツリーの前にSQL文を表示するかどうか
     pub show_sql: bool,
+    /// `tree` コマンドの出力形式
+    pub format: Format,
+    /// ツリー出力を色付けするかどうか
+    pub use_color: bool,
 }
 
 impl DisplayConfig {
@@ -148,6 +229,8 @@ impl From<&Commands> for DisplayConfig {
                 show_node_type,
                 show_sql_separator,
                 show_sql,
+                format,
+                color,
                 ..
             } => DisplayConfig {
                 show_range: !hide_range,
@@ -157,8 +240,40 @@ impl From<&Commands> for DisplayConfig {
                 show_node_type: *show_node_type,
                 show_sql_separator: *show_sql_separator,
                 show_sql: *show_sql,
+                format: *format,
+                use_color: color.resolve(),
             },
             Commands::Tokens { .. } => unreachable!(),
+            Commands::Query {
+                hide_range,
+                hide_text,
+                ..
+            } => DisplayConfig {
+                show_range: !hide_range,
+                show_all_text: !hide_text,
+                show_node_text: false,
+                show_token_text: !hide_text,
+                show_node_type: false,
+                show_sql_separator: false,
+                show_sql: false,
+                format: Format::Text,
+                use_color: false,
+            },
+            Commands::Diff {
+                hide_range,
+                show_text,
+                ..
+            } => DisplayConfig {
+                show_range: !hide_range,
+                show_all_text: *show_text,
+                show_node_text: false,
+                show_token_text: true,
+                show_node_type: false,
+                show_sql_separator: false,
+                show_sql: false,
+                format: Format::Text,
+                use_color: false,
+            },
         }
     }
 }
@@ -183,9 +298,9 @@ pub struct Cli {
 pub enum Commands {
     /// CST（具象構文木）を表示
     Tree {
-        /// 表示する木の深さ範囲（例: 3, 1..3, 1..=3, ..3, ..=3, 3..）
+        /// 表示する木の深さ範囲（例: 3, 1..3, 1..=3, ..3, ..=3, 3.., 0,2,4..6）
         #[arg(short, long, value_name = "DEPTH")]
-        depth: Option<DepthRange>,
+        depth: Option<DepthSelection>,
 
         /// ノードの範囲情報を表示しない
         #[arg(long, default_value = "false")]
@@ -214,6 +329,14 @@ pub enum Commands {
         /// 各ツリーの前にSQL文を表示する
         #[arg(long, default_value = "false")]
         show_sql: bool,
+
+        /// 出力形式
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+
+        /// 出力に色を付けるかどうか
+        #[arg(long, value_enum, default_value = "auto")]
+        color: Color,
     },
 
     /// トークン列を表示
@@ -226,11 +349,39 @@ pub enum Commands {
         #[arg(long, default_value = "false")]
         hide_text: bool,
     },
+
+    /// tree-sitter風のクエリパターンにマッチするノードを表示
+    Query {
+        /// マッチさせるクエリパターン（例: '(select_statement (target_list) @targets)'）
+        pattern: String,
+
+        /// マッチしたノードの範囲情報を表示しない
+        #[arg(long, default_value = "false")]
+        hide_range: bool,
+
+        /// マッチしたノードのテキストを表示しない
+        #[arg(long, default_value = "false")]
+        hide_text: bool,
+    },
+
+    /// 2つのSQLファイルをパースしてCSTの構造的な差分を表示
+    Diff {
+        /// 比較対象となるもう一方のSQLファイルのパス
+        other: PathBuf,
+
+        /// ノードの範囲情報を表示しない
+        #[arg(long, default_value = "false")]
+        hide_range: bool,
+
+        /// すべてのノードのテキストを表示する
+        #[arg(long, default_value = "false")]
+        show_text: bool,
+    },
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cli::{Cli, DepthRange, Endpoint};
+    use crate::cli::{Cli, DepthRange, DepthSelection, Endpoint};
 
     #[test]
     fn verify_cli() {
@@ -345,4 +496,63 @@ mod tests {
             assert!(range.contains(usize::MAX));
         }
     }
+
+    mod depth_selection {
+        use super::*;
+
+        #[test]
+        fn test_single_segment_behaves_like_depth_range() {
+            let selection: DepthSelection = "1..3".parse().unwrap();
+            assert!(!selection.contains(0));
+            assert!(selection.contains(1));
+            assert!(selection.contains(2));
+            assert!(!selection.contains(3));
+        }
+
+        #[test]
+        fn test_disjoint_segments() {
+            let selection: DepthSelection = "0,2,4..6".parse().unwrap();
+            assert!(selection.contains(0));
+            assert!(!selection.contains(1));
+            assert!(selection.contains(2));
+            assert!(!selection.contains(3));
+            assert!(selection.contains(4));
+            assert!(selection.contains(5));
+            assert!(!selection.contains(6));
+        }
+
+        #[test]
+        fn test_overlapping_segments() {
+            let selection: DepthSelection = "1..4,2..6".parse().unwrap();
+            assert!(!selection.contains(0));
+            assert!(selection.contains(1));
+            assert!(selection.contains(3));
+            assert!(selection.contains(5));
+            assert!(!selection.contains(6));
+        }
+
+        #[test]
+        fn test_adjacent_segments() {
+            let selection: DepthSelection = "1..2,2..3".parse().unwrap();
+            assert!(!selection.contains(0));
+            assert!(selection.contains(1));
+            assert!(selection.contains(2));
+            assert!(!selection.contains(3));
+        }
+
+        #[test]
+        fn test_unbounded_segment_in_union() {
+            let selection: DepthSelection = "1..2,5..".parse().unwrap();
+            assert!(selection.contains(1));
+            assert!(!selection.contains(3));
+            assert!(selection.contains(5));
+            assert!(selection.contains(usize::MAX));
+        }
+
+        #[test]
+        fn test_invalid_segment_propagates_error() {
+            assert!("0,a".parse::<DepthSelection>().is_err());
+            assert!("0,5..3".parse::<DepthSelection>().is_err());
+        }
+    }
 }
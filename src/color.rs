@@ -0,0 +1,59 @@
+//! ツリー出力に色を付けるための最小限のANSIカラーリング
+
+/// 色を塗り分ける対象のカテゴリ
+#[derive(Debug, Clone, Copy)]
+pub enum Role {
+    /// 非終端ノードの種類
+    NodeKind,
+    /// トークンの種類
+    TokenKind,
+    /// `range()` の表示
+    Range,
+    /// 引用符付きのテキスト
+    Text,
+}
+
+impl Role {
+    fn code(self) -> &'static str {
+        match self {
+            Role::NodeKind => "34",  // 青
+            Role::TokenKind => "32", // 緑
+            Role::Range => "90",     // グレー
+            Role::Text => "33",      // 黄
+        }
+    }
+}
+
+/// `enabled` が `true` の場合のみ `s` をANSIエスケープシーケンスで装飾する
+pub fn paint(role: Role, s: &str, enabled: bool) -> String {
+    if !enabled {
+        return s.to_string();
+    }
+    format!("\x1b[{}m{}\x1b[0m", role.code(), s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paint_disabled_returns_plain_text() {
+        assert_eq!(paint(Role::NodeKind, "select_statement", false), "select_statement");
+    }
+
+    #[test]
+    fn test_paint_enabled_wraps_in_ansi_escape() {
+        assert_eq!(
+            paint(Role::NodeKind, "select_statement", true),
+            "\x1b[34mselect_statement\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_paint_uses_distinct_codes_per_role() {
+        assert_eq!(Role::NodeKind.code(), "34");
+        assert_eq!(Role::TokenKind.code(), "32");
+        assert_eq!(Role::Range.code(), "90");
+        assert_eq!(Role::Text.code(), "33");
+    }
+}
@@ -0,0 +1,327 @@
+//! 2つのCST間の構造的な差分を表示する
+
+use crate::cli::DisplayConfig;
+use postgresql_cst_parser::tree_sitter::Node;
+
+fn collect_children(node: Node) -> Vec<Node> {
+    let mut children = Vec::new();
+    let mut cursor = node.walk();
+    if cursor.goto_first_child() {
+        loop {
+            children.push(cursor.node());
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+    children
+}
+
+/// 子ノード同士の対応付けに使う署名。トークンは種類とテキストの両方が一致して
+/// はじめて「同じ」とみなす
+fn child_signature(node: &Node) -> (&str, Option<String>) {
+    if node.child_count() == 0 {
+        (node.kind(), Some(node.text().to_string()))
+    } else {
+        (node.kind(), None)
+    }
+}
+
+/// 子ノード列の最長共通部分列を取り、対応する添字のペア（片方が `None` の場合は
+/// 挿入/削除）を順番に並べて返す
+fn align_children(old: &[Node], new: &[Node]) -> Vec<(Option<usize>, Option<usize>)> {
+    let old_signatures: Vec<_> = old.iter().map(child_signature).collect();
+    let new_signatures: Vec<_> = new.iter().map(child_signature).collect();
+    align_by_signature(&old_signatures, &new_signatures)
+}
+
+/// `align_children` の本体。署名列だけを扱う純粋関数として切り出すことで、
+/// 実際のCSTノードなしにLCS整列ロジックを単体テストできるようにしている
+fn align_by_signature<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(Option<usize>, Option<usize>)> {
+    let n = old.len();
+    let m = new.len();
+
+    let mut dp = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            dp[i][j] = if old[i] == new[j] {
+                dp[i + 1][j + 1] + 1
+            } else {
+                dp[i + 1][j].max(dp[i][j + 1])
+            };
+        }
+    }
+
+    let mut alignment = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            alignment.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if dp[i + 1][j] >= dp[i][j + 1] {
+            alignment.push((Some(i), None));
+            i += 1;
+        } else {
+            alignment.push((None, Some(j)));
+            j += 1;
+        }
+    }
+    while i < n {
+        alignment.push((Some(i), None));
+        i += 1;
+    }
+    while j < m {
+        alignment.push((None, Some(j)));
+        j += 1;
+    }
+
+    alignment
+}
+
+fn describe(node: Node, config: &DisplayConfig) -> String {
+    let mut description = node.kind().to_string();
+    if config.show_range {
+        description.push_str(&format!(" {}", node.range()));
+    }
+    if config.should_show_text(node.child_count() == 0) {
+        description.push_str(&format!(" \"{}\"", node.text().escape_debug()));
+    }
+    description
+}
+
+const DIFF_INDENT_SIZE: usize = 2;
+
+fn diff_node(old: Node, new: Node, path: &str, depth: usize, config: &DisplayConfig, any_diff: &mut bool) {
+    let indent = " ".repeat(depth * DIFF_INDENT_SIZE);
+
+    if old.kind() != new.kind() {
+        *any_diff = true;
+        println!(
+            "{}{} replace: {} -> {}",
+            indent,
+            path,
+            describe(old, config),
+            describe(new, config)
+        );
+        return;
+    }
+
+    let old_children = collect_children(old);
+    let new_children = collect_children(new);
+
+    // どちらか一方だけがトークン（子を持たない）の場合は種類が同じでも構造が異なる
+    if old_children.is_empty() || new_children.is_empty() {
+        if old_children.is_empty() != new_children.is_empty() || old.text() != new.text() {
+            *any_diff = true;
+            println!(
+                "{}{} replace: {} -> {}",
+                indent,
+                path,
+                describe(old, config),
+                describe(new, config)
+            );
+        }
+        return;
+    }
+
+    for (old_idx, new_idx) in align_children(&old_children, &new_children) {
+        match (old_idx, new_idx) {
+            (Some(oi), Some(ni)) => {
+                let child_path = format!("{}/{}", path, oi);
+                diff_node(
+                    old_children[oi],
+                    new_children[ni],
+                    &child_path,
+                    depth + 1,
+                    config,
+                    any_diff,
+                );
+            }
+            (Some(oi), None) => {
+                *any_diff = true;
+                let child_path = format!("{}/{}", path, oi);
+                let child_indent = " ".repeat((depth + 1) * DIFF_INDENT_SIZE);
+                println!(
+                    "{}{} delete: {}",
+                    child_indent,
+                    child_path,
+                    describe(old_children[oi], config)
+                );
+            }
+            (None, Some(ni)) => {
+                *any_diff = true;
+                let child_path = format!("{}/{}", path, ni);
+                let child_indent = " ".repeat((depth + 1) * DIFF_INDENT_SIZE);
+                println!(
+                    "{}{} insert: {}",
+                    child_indent,
+                    child_path,
+                    describe(new_children[ni], config)
+                );
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+/// `old` と `new` のCSTを比較し、差分を標準出力に表示する
+pub fn print_diff(old: Node, new: Node, config: &DisplayConfig) {
+    let mut any_diff = false;
+    diff_node(old, new, "", 0, config, &mut any_diff);
+    if !any_diff {
+        println!("差分はありません");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Format;
+    use postgresql_cst_parser::tree_sitter::parse;
+
+    fn test_config() -> DisplayConfig {
+        DisplayConfig {
+            show_range: false,
+            show_all_text: false,
+            show_node_text: false,
+            show_token_text: true,
+            show_node_type: false,
+            show_sql_separator: false,
+            show_sql: false,
+            format: Format::Text,
+            use_color: false,
+        }
+    }
+
+    mod align_by_signature_tests {
+        use super::*;
+
+        #[test]
+        fn test_identical_sequences_align_pairwise() {
+            let old = vec![("a", None::<String>), ("b", None)];
+            let new = old.clone();
+            assert_eq!(
+                align_by_signature(&old, &new),
+                vec![(Some(0), Some(0)), (Some(1), Some(1))]
+            );
+        }
+
+        #[test]
+        fn test_appended_child_is_insert() {
+            let old = vec![("a", None::<String>)];
+            let new = vec![("a", None), ("b", None)];
+            assert_eq!(
+                align_by_signature(&old, &new),
+                vec![(Some(0), Some(0)), (None, Some(1))]
+            );
+        }
+
+        #[test]
+        fn test_removed_child_is_delete() {
+            let old = vec![("a", None::<String>), ("b", None)];
+            let new = vec![("a", None)];
+            assert_eq!(
+                align_by_signature(&old, &new),
+                vec![(Some(0), Some(0)), (Some(1), None)]
+            );
+        }
+
+        #[test]
+        fn test_unrelated_sequences_are_delete_then_insert() {
+            let old = vec![("a", None::<String>)];
+            let new = vec![("b", None)];
+            assert_eq!(
+                align_by_signature(&old, &new),
+                vec![(Some(0), None), (None, Some(0))]
+            );
+        }
+
+        #[test]
+        fn test_empty_sequences_align_to_nothing() {
+            let old: Vec<(&str, Option<String>)> = vec![];
+            let new: Vec<(&str, Option<String>)> = vec![];
+            assert!(align_by_signature(&old, &new).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_diff_node_identical_trees_report_no_diff() {
+        let tree = parse("SELECT 1").unwrap();
+        let root = tree.root_node();
+        let config = test_config();
+
+        let mut any_diff = false;
+        diff_node(root, root, "", 0, &config, &mut any_diff);
+        assert!(!any_diff);
+    }
+
+    #[test]
+    fn test_diff_node_changed_literal_is_reported() {
+        let old_tree = parse("SELECT 1").unwrap();
+        let new_tree = parse("SELECT 2").unwrap();
+        let config = test_config();
+
+        let mut any_diff = false;
+        diff_node(
+            old_tree.root_node(),
+            new_tree.root_node(),
+            "",
+            0,
+            &config,
+            &mut any_diff,
+        );
+        assert!(any_diff);
+    }
+
+    #[test]
+    fn test_diff_node_added_column_is_insert() {
+        let old_tree = parse("SELECT 1").unwrap();
+        let new_tree = parse("SELECT 1, 2").unwrap();
+        let config = test_config();
+
+        let mut any_diff = false;
+        diff_node(
+            old_tree.root_node(),
+            new_tree.root_node(),
+            "",
+            0,
+            &config,
+            &mut any_diff,
+        );
+        assert!(any_diff);
+    }
+
+    /// 2つの木を同時にたどり、種類が最初に異なるノードのペアを返す
+    ///
+    /// この文法ではトップレベル文が `parse_toplevel`/`stmtmulti` の再帰的な
+    /// ラッパーに包まれているため、「root の最初の子」が実際の文ノードとは
+    /// 限らない。構造が一致している間は並行にたどり、最初の食い違いを探す。
+    fn first_differing_descendant(old: Node, new: Node) -> Option<(Node, Node)> {
+        if old.kind() != new.kind() {
+            return Some((old, new));
+        }
+
+        let old_children = collect_children(old);
+        let new_children = collect_children(new);
+        old_children
+            .iter()
+            .zip(new_children.iter())
+            .find_map(|(o, n)| first_differing_descendant(*o, *n))
+    }
+
+    #[test]
+    fn test_diff_node_different_statement_kind_is_replace() {
+        let old_tree = parse("SELECT 1").unwrap();
+        let new_tree = parse("DROP TABLE foo").unwrap();
+        let (old_statement, new_statement) =
+            first_differing_descendant(old_tree.root_node(), new_tree.root_node())
+                .expect("trees for different statement kinds should have a differing descendant");
+        assert_ne!(old_statement.kind(), new_statement.kind());
+
+        let config = test_config();
+        let mut any_diff = false;
+        diff_node(old_statement, new_statement, "/0", 1, &config, &mut any_diff);
+        assert!(any_diff);
+    }
+}
@@ -1,7 +1,10 @@
 mod cli;
+mod color;
+mod diff;
+mod query;
 
 use clap::Parser;
-use cli::{Commands, Cli, DepthRange, DisplayConfig, Endpoint};
+use cli::{Color, Commands, Cli, DepthSelection, DisplayConfig, Format};
 use postgresql_cst_parser::tree_sitter::{parse, Node};
 use std::fs;
 use std::process;
@@ -9,7 +12,7 @@ use std::fmt::Write;
 
 const INDENT_SIZE: usize = 2;
 
-fn should_print(depth: usize, range: &Option<DepthRange>) -> bool {
+fn should_print(depth: usize, range: &Option<DepthSelection>) -> bool {
     match range {
         None => true,
         Some(range) => range.contains(depth),
@@ -19,66 +22,205 @@ fn should_print(depth: usize, range: &Option<DepthRange>) -> bool {
 fn print_tree(
     node: Node,
     depth: usize,
-    range: &Option<DepthRange>,
+    range: &Option<DepthSelection>,
     config: &DisplayConfig,
+    sql: &str,
 ) {
-    let mut output = String::new();
-    write_tree(node, depth, range, config, &mut output).expect("writing to string should not fail");
-    print!("{}", output);
+    match config.format {
+        Format::Text => {
+            print_tree_by_statement(node, range, config, sql);
+        }
+        Format::Json => {
+            let json = build_tree_json(node, depth, range, config).unwrap_or_else(|| "null".to_string());
+            println!("{}", json);
+        }
+        Format::Sexp => {
+            let sexp = build_tree_sexp(node, depth, range).unwrap_or_else(|| "()".to_string());
+            println!("{}", sexp);
+        }
+    }
 }
 
-fn write_tree(
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `node` をJSONのノードオブジェクトに変換する。`should_print` に合致しない場合は
+/// `None` を返し、子ノードはフラット化して親に繰り上げる
+fn build_tree_json(
     node: Node,
     depth: usize,
-    range: &Option<DepthRange>,
+    range: &Option<DepthSelection>,
     config: &DisplayConfig,
-    output: &mut String,
-) -> std::fmt::Result {
-    let should_display = should_print(depth, range);
-
-    if should_display {
-        // インデントの基準となる深さを取得
-        let base_depth = match range {
-            Some(range) => match range.start {
-                Endpoint::Inclusive(start) => start,
-                Endpoint::Exclusive(start) => start + 1,
-            },
-            None => 0,
-        };
+) -> Option<String> {
+    if !should_print(depth, range) {
+        return None;
+    }
 
-        // インデント
-        if depth > 0 {
-            // 基準深さからの相対的なインデントを計算
-            let relative_depth = if depth > base_depth {
-                depth - base_depth
-            } else {
-                0
-            };
-            if relative_depth > 0 {
-                write!(output, "{}-+", "-".repeat((relative_depth - 1) * INDENT_SIZE))?;
+    let is_token = node.child_count() == 0;
+    let mut fields = vec![
+        format!("\"kind\":\"{}\"", json_escape(node.kind())),
+        format!("\"is_token\":{}", is_token),
+    ];
+
+    if config.show_range {
+        fields.push(format!(
+            "\"range\":{{\"start\":{},\"end\":{}}}",
+            node.start_byte(),
+            node.end_byte()
+        ));
+    }
+
+    if config.should_show_text(is_token) {
+        fields.push(format!("\"text\":\"{}\"", json_escape(node.text())));
+    }
+
+    fields.push(format!(
+        "\"children\":[{}]",
+        json_children(node, depth, range, config).join(",")
+    ));
+
+    Some(format!("{{{}}}", fields.join(",")))
+}
+
+/// `node` の直接の子を順番にJSON化する。`should_print` に合致しない子はさらに
+/// その子へ展開し、表示対象の子孫だけを平らに並べる
+fn json_children(
+    node: Node,
+    depth: usize,
+    range: &Option<DepthSelection>,
+    config: &DisplayConfig,
+) -> Vec<String> {
+    let mut children = Vec::new();
+    let mut cursor = node.walk();
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            match build_tree_json(child, depth + 1, range, config) {
+                Some(json) => children.push(json),
+                None => children.extend(json_children(child, depth + 1, range, config)),
+            }
+            if !cursor.goto_next_sibling() {
+                break;
             }
         }
+    }
+    children
+}
 
-        // ノードの種類
-        let is_token = node.child_count() == 0;
-        write!(output, "{}", node.kind())?;
-        if config.show_node_type {
-            write!(output, " ({})", if is_token { "Token" } else { "Node" })?;
-        }
+/// `node` をS式の文字列に変換する。`should_print` に合致しない場合は `None` を
+/// 返し、子ノードはフラット化して親に繰り上げる
+fn build_tree_sexp(node: Node, depth: usize, range: &Option<DepthSelection>) -> Option<String> {
+    if !should_print(depth, range) {
+        return None;
+    }
 
-        // 範囲情報
-        if config.show_range {
-            write!(output, " {}", node.range())?;
+    let children = sexp_children(node, depth, range);
+    if children.is_empty() {
+        Some(node.kind().to_string())
+    } else {
+        Some(format!("({} {})", node.kind(), children.join(" ")))
+    }
+}
+
+fn sexp_children(node: Node, depth: usize, range: &Option<DepthSelection>) -> Vec<String> {
+    let mut children = Vec::new();
+    let mut cursor = node.walk();
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            match build_tree_sexp(child, depth + 1, range) {
+                Some(sexp) => children.push(sexp),
+                None => children.extend(sexp_children(child, depth + 1, range)),
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
         }
+    }
+    children
+}
+
+fn write_node_line(
+    node: Node,
+    depth: usize,
+    range: &Option<DepthSelection>,
+    config: &DisplayConfig,
+    output: &mut String,
+) -> std::fmt::Result {
+    if !should_print(depth, range) {
+        return Ok(());
+    }
 
-        // テキスト
-        if config.should_show_text(is_token) {
-            write!(output, " \"{}\"", node.text().escape_debug())?;
+    // インデントの基準となる深さを取得
+    let base_depth = match range {
+        Some(range) => range.base_depth(),
+        None => 0,
+    };
+
+    // インデント
+    if depth > 0 {
+        // 基準深さからの相対的なインデントを計算
+        let relative_depth = if depth > base_depth {
+            depth - base_depth
+        } else {
+            0
+        };
+        if relative_depth > 0 {
+            write!(output, "{}-+", "-".repeat((relative_depth - 1) * INDENT_SIZE))?;
         }
+    }
+
+    // ノードの種類
+    let is_token = node.child_count() == 0;
+    let kind_role = if is_token {
+        color::Role::TokenKind
+    } else {
+        color::Role::NodeKind
+    };
+    write!(output, "{}", color::paint(kind_role, node.kind(), config.use_color))?;
+    if config.show_node_type {
+        write!(output, " ({})", if is_token { "Token" } else { "Node" })?;
+    }
+
+    // 範囲情報
+    if config.show_range {
+        let range = node.range().to_string();
+        write!(output, " {}", color::paint(color::Role::Range, &range, config.use_color))?;
+    }
 
-        writeln!(output)?;
+    // テキスト
+    if config.should_show_text(is_token) {
+        let text = format!("\"{}\"", node.text().escape_debug());
+        write!(output, " {}", color::paint(color::Role::Text, &text, config.use_color))?;
     }
 
+    writeln!(output)?;
+
+    Ok(())
+}
+
+fn write_tree(
+    node: Node,
+    depth: usize,
+    range: &Option<DepthSelection>,
+    config: &DisplayConfig,
+    output: &mut String,
+) -> std::fmt::Result {
+    write_node_line(node, depth, range, config, output)?;
+
     let mut cursor = node.walk();
     if cursor.goto_first_child() {
         loop {
@@ -92,6 +234,72 @@ fn write_tree(
     Ok(())
 }
 
+/// トップレベルのSQL文を表すノードの種類
+///
+/// この文法では複数文は `parse_toplevel` が `stmtmulti: stmtmulti ';' stmt` を
+/// 再帰的にラップする形で表現されるため、「文」の境界は`root`の直接の子では
+/// なく、種類が `STMT_NODE_KIND` であるノードとして木の中から探す必要がある
+const STMT_NODE_KIND: &str = "stmt";
+
+/// 木全体を再帰的に出力しつつ、`STMT_NODE_KIND` のノードに差し掛かるたびに
+/// 元のSQLテキストを直前に挟み込む（区切りの空行は先頭の文以外の前にだけ挟む）
+fn write_tree_with_statements(
+    node: Node,
+    depth: usize,
+    range: &Option<DepthSelection>,
+    config: &DisplayConfig,
+    sql: &str,
+    seen_first_statement: &mut bool,
+    output: &mut String,
+) -> std::fmt::Result {
+    if node.kind() == STMT_NODE_KIND {
+        if *seen_first_statement && config.show_sql_separator {
+            writeln!(output)?;
+        }
+        if config.show_sql {
+            writeln!(output, "{}", &sql[node.start_byte()..node.end_byte()])?;
+        }
+        *seen_first_statement = true;
+    }
+
+    write_node_line(node, depth, range, config, output)?;
+
+    let mut cursor = node.walk();
+    if cursor.goto_first_child() {
+        loop {
+            write_tree_with_statements(
+                cursor.node(),
+                depth + 1,
+                range,
+                config,
+                sql,
+                seen_first_statement,
+                output,
+            )?;
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// CST全体を出力する。`show_sql`/`show_sql_separator` が有効な場合は、各トップ
+/// レベル文の直前に元のSQLテキストや区切りの空行を挟む
+fn print_tree_by_statement(
+    root: Node,
+    range: &Option<DepthSelection>,
+    config: &DisplayConfig,
+    sql: &str,
+) {
+    let mut output = String::new();
+    let mut seen_first_statement = false;
+    write_tree_with_statements(root, 0, range, config, sql, &mut seen_first_statement, &mut output)
+        .expect("writing to string should not fail");
+    print!("{}", output);
+}
+
 fn print_tokens(node: Node, hide_range: bool, show_text: bool) {
     let mut cursor = node.walk();
     
@@ -127,6 +335,98 @@ fn print_tokens(node: Node, hide_range: bool, show_text: bool) {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli::Format;
+    use postgresql_cst_parser::tree_sitter::parse;
+
+    fn test_config() -> DisplayConfig {
+        DisplayConfig {
+            show_range: false,
+            show_all_text: false,
+            show_node_text: false,
+            show_token_text: true,
+            show_node_type: false,
+            show_sql_separator: false,
+            show_sql: false,
+            format: Format::Text,
+            use_color: false,
+        }
+    }
+
+    #[test]
+    fn test_build_tree_json_includes_kind_and_children() {
+        let tree = parse("SELECT 1").unwrap();
+        let config = test_config();
+
+        let json = build_tree_json(tree.root_node(), 0, &None, &config).unwrap();
+        assert!(json.contains("\"kind\":\"root\""));
+        assert!(json.contains("\"children\":["));
+    }
+
+    #[test]
+    fn test_build_tree_json_respects_depth_selection() {
+        let tree = parse("SELECT 1").unwrap();
+        let range = Some("0".parse::<DepthSelection>().unwrap());
+        let config = test_config();
+
+        let json = build_tree_json(tree.root_node(), 0, &range, &config).unwrap();
+        // 深さ0のノード自身は表示されるが、子はすべて深さ1以降なので除外される
+        assert!(json.contains("\"kind\":\"root\""));
+        assert!(json.ends_with("\"children\":[]}"));
+    }
+
+    #[test]
+    fn test_build_tree_json_out_of_range_root_is_none() {
+        let tree = parse("SELECT 1").unwrap();
+        let range = Some("5".parse::<DepthSelection>().unwrap());
+        let config = test_config();
+
+        assert_eq!(build_tree_json(tree.root_node(), 0, &range, &config), None);
+    }
+
+    #[test]
+    fn test_build_tree_sexp_wraps_kind_and_children() {
+        let tree = parse("SELECT 1").unwrap();
+
+        let sexp = build_tree_sexp(tree.root_node(), 0, &None).unwrap();
+        assert!(sexp.starts_with("(root "));
+        assert!(sexp.ends_with(')'));
+    }
+
+    #[test]
+    fn test_build_tree_sexp_out_of_range_root_is_none() {
+        let tree = parse("SELECT 1").unwrap();
+        let range = Some("5".parse::<DepthSelection>().unwrap());
+
+        assert_eq!(build_tree_sexp(tree.root_node(), 0, &range), None);
+    }
+
+    #[test]
+    fn test_write_node_line_without_color_has_no_escape_codes() {
+        let tree = parse("SELECT 1").unwrap();
+        let mut config = test_config();
+        config.use_color = false;
+
+        let mut output = String::new();
+        write_node_line(tree.root_node(), 0, &None, &config, &mut output).unwrap();
+        assert!(!output.contains('\x1b'));
+        assert!(output.contains("root"));
+    }
+
+    #[test]
+    fn test_write_node_line_with_color_wraps_kind_in_escape_codes() {
+        let tree = parse("SELECT 1").unwrap();
+        let mut config = test_config();
+        config.use_color = true;
+
+        let mut output = String::new();
+        write_node_line(tree.root_node(), 0, &None, &config, &mut output).unwrap();
+        assert!(output.contains("\x1b[34mroot\x1b[0m"));
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
 
@@ -157,6 +457,10 @@ fn main() {
         show_node_text: false,
         hide_token_text: false,
         show_node_type: false,
+        show_sql_separator: false,
+        show_sql: false,
+        format: Format::Text,
+        color: Color::Auto,
     }) {
         Commands::Tree {
             depth,
@@ -165,6 +469,10 @@ fn main() {
             show_node_text,
             hide_token_text,
             show_node_type,
+            show_sql_separator,
+            show_sql,
+            format,
+            color,
         } => {
             let command = Commands::Tree {
                 depth: depth.clone(),
@@ -173,12 +481,60 @@ fn main() {
                 show_node_text,
                 hide_token_text,
                 show_node_type,
+                show_sql_separator,
+                show_sql,
+                format,
+                color,
             };
             let config = DisplayConfig::from(&command);
-            print_tree(root_node, 0, &depth, &config);
+            print_tree(root_node, 0, &depth, &config, &sql);
         }
         Commands::Tokens { hide_range, hide_text } => {
             print_tokens(root_node, hide_range, !hide_text);
         }
+        Commands::Query {
+            pattern,
+            hide_range,
+            hide_text,
+        } => {
+            let command = Commands::Query {
+                pattern: pattern.clone(),
+                hide_range,
+                hide_text,
+            };
+            let config = DisplayConfig::from(&command);
+            if let Err(err) = query::run_query(root_node, &pattern, &config) {
+                eprintln!("クエリのパースに失敗しました: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Diff {
+            other,
+            hide_range,
+            show_text,
+        } => {
+            let other_sql = match fs::read_to_string(&other) {
+                Ok(content) => content,
+                Err(err) => {
+                    eprintln!("ファイルの読み込みに失敗しました: {}", err);
+                    process::exit(1);
+                }
+            };
+            let other_tree = match parse(&other_sql) {
+                Ok(tree) => tree,
+                Err(err) => {
+                    eprintln!("SQLのパースに失敗しました: {:?}", err);
+                    process::exit(1);
+                }
+            };
+
+            let command = Commands::Diff {
+                other: other.clone(),
+                hide_range,
+                show_text,
+            };
+            let config = DisplayConfig::from(&command);
+            diff::print_diff(root_node, other_tree.root_node(), &config);
+        }
     }
 }
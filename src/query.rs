@@ -0,0 +1,291 @@
+//! tree-sitterクエリ風のパターンでCSTを検索するための簡易クエリエンジン
+
+use crate::cli::DisplayConfig;
+use postgresql_cst_parser::tree_sitter::Node;
+
+/// クエリパターンを解析した結果のマッチャー
+///
+/// `(select_statement (target_list) @targets)` のようなパターンを表現する。
+/// `kind` が `None` の場合は `_`（ワイルドカード）を表す。
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Matcher {
+    kind: Option<String>,
+    children: Vec<Matcher>,
+    capture: Option<String>,
+}
+
+fn tokenize(pattern: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '(' | ')' => {
+                tokens.push(c.to_string());
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '(' || c == ')' || c.is_whitespace() {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(word);
+            }
+        }
+    }
+
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_pattern(&mut self) -> Result<Matcher, String> {
+        match self.next() {
+            Some("(") => {}
+            Some(other) => return Err(format!("'(' を期待しましたが '{}' が見つかりました", other)),
+            None => return Err("'(' を期待しましたが入力が終了しました".to_string()),
+        }
+
+        let kind_token = self
+            .next()
+            .ok_or_else(|| "ノード種別を期待しましたが入力が終了しました".to_string())?;
+        let kind = if kind_token == "_" {
+            None
+        } else {
+            Some(kind_token.to_string())
+        };
+
+        let mut children = Vec::new();
+        loop {
+            match self.peek() {
+                Some("(") => {
+                    let mut child = self.parse_pattern()?;
+                    if let Some(name) = self.peek().and_then(|t| t.strip_prefix('@')) {
+                        child.capture = Some(name.to_string());
+                        self.next();
+                    }
+                    children.push(child);
+                }
+                Some(")") => {
+                    self.next();
+                    break;
+                }
+                Some(other) if other.starts_with('@') => {
+                    return Err(format!("'{}' の前に対応するパターンがありません", other));
+                }
+                Some(other) => return Err(format!("予期しないトークン '{}'", other)),
+                None => return Err("')' を期待しましたが入力が終了しました".to_string()),
+            }
+        }
+
+        Ok(Matcher {
+            kind,
+            children,
+            capture: None,
+        })
+    }
+}
+
+fn parse_query(pattern: &str) -> Result<Matcher, String> {
+    let tokens = tokenize(pattern);
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+    };
+
+    let mut root = parser.parse_pattern()?;
+    if let Some(name) = parser.peek().and_then(|t| t.strip_prefix('@')) {
+        root.capture = Some(name.to_string());
+        parser.next();
+    }
+
+    if parser.pos != tokens.len() {
+        return Err(format!(
+            "'{}' 以降に余分なトークンがあります",
+            tokens[parser.pos..].join(" ")
+        ));
+    }
+
+    Ok(root)
+}
+
+/// `node` が `matcher` にマッチするかどうかを判定し、マッチした場合はキャプチャを `captures` に積む
+///
+/// 子ノードの対応付けは「順序付き部分列」として扱う: 各子マッチャーは、それより前の
+/// 子マッチャーが対応付けた子ノードより後ろにある子ノードの中から最初に一致したものに
+/// 割り当てられる（間にある一致しない兄弟はスキップしてよい）。
+fn match_node(node: Node, matcher: &Matcher, captures: &mut Vec<(String, Node)>) -> bool {
+    if let Some(kind) = &matcher.kind {
+        if node.kind() != kind {
+            return false;
+        }
+    }
+
+    if !matcher.children.is_empty() {
+        let mut cursor = node.walk();
+        if !cursor.goto_first_child() {
+            return false;
+        }
+
+        let mut remaining = &matcher.children[..];
+        loop {
+            if remaining.is_empty() {
+                break;
+            }
+
+            let mut child_captures = Vec::new();
+            if match_node(cursor.node(), &remaining[0], &mut child_captures) {
+                captures.append(&mut child_captures);
+                remaining = &remaining[1..];
+            }
+
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+
+        if !remaining.is_empty() {
+            return false;
+        }
+    }
+
+    if let Some(name) = &matcher.capture {
+        captures.push((name.clone(), node));
+    }
+
+    true
+}
+
+fn print_capture(name: &str, node: Node, config: &DisplayConfig) {
+    print!("@{}", name);
+    if config.show_range {
+        print!(" {}", node.range());
+    }
+    if config.should_show_text(node.child_count() == 0) {
+        print!(" \"{}\"", node.text().escape_debug());
+    }
+    println!();
+}
+
+fn walk_and_match(node: Node, matcher: &Matcher, config: &DisplayConfig) {
+    let mut captures = Vec::new();
+    if match_node(node, matcher, &mut captures) {
+        for (name, captured) in &captures {
+            print_capture(name, *captured, config);
+        }
+    }
+
+    let mut cursor = node.walk();
+    if cursor.goto_first_child() {
+        loop {
+            walk_and_match(cursor.node(), matcher, config);
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+/// CST全体に対して `pattern` を走らせ、マッチしたキャプチャを表示する
+pub fn run_query(root: Node, pattern: &str, config: &DisplayConfig) -> Result<(), String> {
+    let matcher = parse_query(pattern)?;
+    walk_and_match(root, &matcher, config);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgresql_cst_parser::tree_sitter::parse;
+
+    #[test]
+    fn test_parse_query_wildcard() {
+        let matcher = parse_query("(_)").unwrap();
+        assert_eq!(matcher.kind, None);
+        assert!(matcher.children.is_empty());
+    }
+
+    #[test]
+    fn test_parse_query_capture() {
+        let matcher = parse_query("(_) @root").unwrap();
+        assert_eq!(matcher.capture, Some("root".to_string()));
+    }
+
+    #[test]
+    fn test_parse_query_nested_capture() {
+        let matcher = parse_query("(_ (_) @first_child)").unwrap();
+        assert_eq!(matcher.children.len(), 1);
+        assert_eq!(matcher.children[0].capture, Some("first_child".to_string()));
+    }
+
+    #[test]
+    fn test_parse_query_malformed_missing_closing_paren() {
+        assert!(parse_query("(select_statement").is_err());
+    }
+
+    #[test]
+    fn test_parse_query_malformed_capture_without_pattern() {
+        assert!(parse_query("(_ @orphan)").is_err());
+    }
+
+    #[test]
+    fn test_parse_query_malformed_trailing_tokens() {
+        assert!(parse_query("(_) extra").is_err());
+    }
+
+    #[test]
+    fn test_match_node_wildcard_captures_root() {
+        let tree = parse("SELECT 1").unwrap();
+        let root = tree.root_node();
+        let matcher = parse_query("(_) @root").unwrap();
+
+        let mut captures = Vec::new();
+        assert!(match_node(root, &matcher, &mut captures));
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0].0, "root");
+    }
+
+    #[test]
+    fn test_match_node_ordered_subsequence_captures_child() {
+        let tree = parse("SELECT 1").unwrap();
+        let root = tree.root_node();
+        let matcher = parse_query("(_ (_) @first_child)").unwrap();
+
+        let mut captures = Vec::new();
+        assert!(match_node(root, &matcher, &mut captures));
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0].0, "first_child");
+    }
+
+    #[test]
+    fn test_match_node_fails_on_unmatched_child_kind() {
+        let tree = parse("SELECT 1").unwrap();
+        let root = tree.root_node();
+        let matcher = parse_query("(_ (this_kind_does_not_exist))").unwrap();
+
+        let mut captures = Vec::new();
+        assert!(!match_node(root, &matcher, &mut captures));
+        assert!(captures.is_empty());
+    }
+}